@@ -1,4 +1,5 @@
 use crate::crypto::HashOf;
+use crate::store::{BlockStore, InMemoryBlockStore, StoreResult};
 use serde::{Deserialize, Serialize};
 
 pub type EpochNum = u64;
@@ -47,19 +48,73 @@ where
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct BlockChain<T> {
+/// The height up to which `blocks` satisfies Streamlet's finalization rule:
+/// the furthest point such that some three consecutive blocks have epochs
+/// `e, e+1, e+2`, which finalizes the block at epoch `e+1` and everything
+/// before it. The genesis block is epoch 0, so it can take part in the first
+/// such window.
+pub fn finalized_height<T>(blocks: &[Block<T>]) -> BlockHeight {
+    let mut finalized = blocks.len().min(1);
+    for (i, window) in blocks.windows(3).enumerate() {
+        if window[1].epoch == window[0].epoch + 1 && window[2].epoch == window[1].epoch + 1 {
+            finalized = i + 2;
+        }
+    }
+    finalized
+}
+
+#[derive(Clone)]
+pub struct BlockChain<T, S: BlockStore<T> = InMemoryBlockStore<T>> {
     blocks: Vec<Block<T>>,
+    store: S,
 }
 
-impl<T> BlockChain<T>
+impl<T: std::fmt::Debug, S: BlockStore<T>> std::fmt::Debug for BlockChain<T, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlockChain").field("blocks", &self.blocks).finish()
+    }
+}
+
+impl<T> BlockChain<T, InMemoryBlockStore<T>>
 where
     T: Serialize + Clone + PartialEq + Eq + std::fmt::Debug,
 {
+    /// A chain backed by an in-memory store, for tests and scratch use.
     pub fn new() -> Self {
-        let mut blocks = Vec::new();
-        blocks.push(Block::genesis_block());
-        BlockChain { blocks }
+        Self::with_store(InMemoryBlockStore::new())
+    }
+}
+
+impl<T, S> BlockChain<T, S>
+where
+    T: Serialize + Clone + PartialEq + Eq + std::fmt::Debug,
+    S: BlockStore<T>,
+{
+    /// A chain backed by `store`, persisting the genesis block to it.
+    pub fn with_store(mut store: S) -> Self {
+        let genesis = Block::genesis_block();
+        store
+            .append(&genesis)
+            .expect("a fresh store must accept the genesis block");
+        BlockChain {
+            blocks: vec![genesis],
+            store,
+        }
+    }
+
+    /// Reconstructs a chain from blocks already durably persisted in `store`,
+    /// without re-appending them. Used to recover a node after a crash.
+    pub fn recover(store: S) -> StoreResult<Self> {
+        let mut blocks = store.load_finalized_prefix()?;
+        let mut height = blocks.len() + 1;
+        while let Some(block) = store.get_by_height(height)? {
+            blocks.push(block);
+            height += 1;
+        }
+        if blocks.is_empty() {
+            blocks.push(Block::genesis_block());
+        }
+        Ok(BlockChain { blocks, store })
     }
 
     pub fn get_latest_block_hash(&self) -> HashOf<Block<T>> {
@@ -71,11 +126,30 @@ where
         self.blocks.len()
     }
 
-    pub fn add_block(&mut self, block: &Block<T>) {
+    pub fn add_block(&mut self, block: &Block<T>) -> StoreResult<()> {
         // TODO error handling
         if block.prev_hash.as_ref().unwrap() != &self.get_latest_block_hash() {
             panic!();
         }
+        self.store.append(block)?;
         self.blocks.push(block.clone());
+        Ok(())
+    }
+
+    /// Splits off an independently-stored copy of this chain, for the moment
+    /// a competing tip is about to diverge from it. `clone()` alone would
+    /// share the same backing store (see `InMemoryBlockStore`'s doc
+    /// comment), so two diverging chains would interleave their writes into
+    /// one height-keyed store and corrupt each other's blocks on `recover`.
+    pub fn fork(&self) -> StoreResult<Self> {
+        Ok(BlockChain {
+            blocks: self.blocks.clone(),
+            store: self.store.forked(&self.blocks)?,
+        })
+    }
+
+    /// The blocks in the chain, in order from genesis to tip.
+    pub fn blocks(&self) -> &[Block<T>] {
+        &self.blocks
     }
 }
@@ -0,0 +1,5 @@
+pub mod blockchain;
+pub mod crypto;
+pub mod node;
+pub mod spec;
+pub mod store;
@@ -1,31 +1,272 @@
-use crate::blockchain::{Block, BlockChain, BlockHeight, EpochNum, INITIAL_EPOCH};
-use crate::crypto::{HashOf, Keypair, PublicKey, Signed};
-use serde::Serialize;
+use crate::blockchain::{finalized_height, Block, BlockChain, BlockHeight, EpochNum, INITIAL_EPOCH};
+use crate::crypto::{self, BlsKeypair, BlsPublicKey, BlsSignature, HashOf, Keypair, PublicKey, Signed};
+use crate::spec::{self, ChainSpec, SpecResult};
+use crate::store::{BlockStore, InMemoryBlockStore, StoreResult};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use snafu::{OptionExt, ResultExt};
 use std::collections::{BTreeMap, HashMap, HashSet};
 
 pub type NodeID = usize;
 
+/// A single node's signed vote for a block, carrying both the ed25519
+/// signature that authenticates it and a BLS signature over the same block
+/// that can later be combined with other voters' into a `NotarizationCertificate`.
 #[derive(Debug)]
-struct Message<T> {
+struct VoteMessage<T> {
     pub recipients: HashSet<NodeID>,
     pub vote: Signed<Block<T>>,
     pub voter: NodeID,
+    pub bls_sig: BlsSignature,
 }
 
+/// A notarization certificate being gossiped to a node's peers, replacing
+/// the O(n) raw-vote echoes that would otherwise be needed to reach quorum.
 #[derive(Debug)]
-struct Node<T> {
+struct CertificateMessage<T> {
+    pub recipients: HashSet<NodeID>,
+    pub certificate: NotarizationCertificate<T>,
+}
+
+#[derive(Debug)]
+enum Message<T> {
+    Vote(VoteMessage<T>),
+    Certificate(CertificateMessage<T>),
+}
+
+impl<T> Message<T> {
+    fn recipients(&self) -> &HashSet<NodeID> {
+        match self {
+            Message::Vote(m) => &m.recipients,
+            Message::Certificate(m) => &m.recipients,
+        }
+    }
+}
+
+/// Per-block notarization bookkeeping: who has voted so far, their BLS
+/// signatures (to be aggregated once quorum is reached), and whether a
+/// certificate has already been built and broadcast for this block.
+#[derive(Debug, Default)]
+struct VoteTally {
+    voters: HashSet<NodeID>,
+    signatures: Vec<(NodeID, BlsSignature)>,
+    certified: bool,
+}
+
+struct Node<T, S: BlockStore<T> = InMemoryBlockStore<T>> {
     epoch_num: EpochNum,
     node_id: NodeID,
     node_set_info: NodeSetInfo,
-    chains: BTreeMap<BlockHeight, Vec<BlockChain<T>>>,
+    chains: BTreeMap<BlockHeight, Vec<BlockChain<T, S>>>,
     keypair: Keypair,
+    bls_keypair: BlsKeypair,
     proposal_recieved: bool,
-    votes: HashMap<HashOf<Block<T>>, (u64, HashSet<NodeID>)>,
+    votes: HashMap<HashOf<Block<T>>, VoteTally>,
+    finalized_height: BlockHeight,
+    finalized_blocks: Vec<Block<T>>,
+    subscribers: Vec<Subscriber<T>>,
+    votes_by_epoch_voter: HashMap<(EpochNum, NodeID), Signed<Block<T>>>,
+    equivocators: HashSet<NodeID>,
+}
+
+impl<T: std::fmt::Debug, S: BlockStore<T> + std::fmt::Debug> std::fmt::Debug for Node<T, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Node")
+            .field("epoch_num", &self.epoch_num)
+            .field("node_id", &self.node_id)
+            .field("node_set_info", &self.node_set_info)
+            .field("chains", &self.chains)
+            .field("proposal_recieved", &self.proposal_recieved)
+            .field("votes", &self.votes)
+            .field("finalized_height", &self.finalized_height)
+            .field("finalized_blocks", &self.finalized_blocks)
+            .field("votes_by_epoch_voter", &self.votes_by_epoch_voter)
+            .field("equivocators", &self.equivocators)
+            .finish()
+    }
+}
+
+/// Returned when appending a block advances the finalized prefix of the chain.
+#[derive(Debug, Clone)]
+pub struct BlockFinalized<T> {
+    pub block_hash: HashOf<Block<T>>,
+    pub height: BlockHeight,
+}
+
+/// Verifiable evidence that `voter` signed two different blocks for the same
+/// `epoch`. Anyone holding a `NodeSetInfo` can check this independently: both
+/// signatures verify against `voter`'s public key, and `first`/`second` carry
+/// different block hashes for the same epoch, so the proof doesn't require
+/// trusting whichever node reports it.
+#[derive(Debug, Clone)]
+pub struct EquivocationProof<T> {
+    pub epoch: EpochNum,
+    pub voter: NodeID,
+    pub first: Signed<Block<T>>,
+    pub second: Signed<Block<T>>,
+}
+
+/// Protocol-progress events a `Node` emits as it reaches each stage, so an
+/// external application can observe consensus progressing without polling
+/// internal state.
+#[derive(Debug, Clone)]
+pub enum StreamletEvent<T> {
+    /// A valid proposal for `epoch` was received (and voted for).
+    ProposalReceived {
+        epoch: EpochNum,
+        block_hash: HashOf<Block<T>>,
+    },
+    /// `block_hash` crossed the notarization vote threshold.
+    BlockNotarized { block_hash: HashOf<Block<T>>, votes: u64 },
+    /// The finalized prefix advanced.
+    BlockFinalized(BlockFinalized<T>),
+    /// A voter was caught signing two different blocks in the same epoch.
+    Equivocation(EquivocationProof<T>),
+}
+
+/// The kind of a `StreamletEvent`, used to filter a subscription without
+/// requiring a filter closure to match on the full event (and its payload).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StreamletEventKind {
+    ProposalReceived,
+    BlockNotarized,
+    BlockFinalized,
+    Equivocation,
+}
+
+impl<T> StreamletEvent<T> {
+    pub fn kind(&self) -> StreamletEventKind {
+        match self {
+            StreamletEvent::ProposalReceived { .. } => StreamletEventKind::ProposalReceived,
+            StreamletEvent::BlockNotarized { .. } => StreamletEventKind::BlockNotarized,
+            StreamletEvent::BlockFinalized(_) => StreamletEventKind::BlockFinalized,
+            StreamletEvent::Equivocation(_) => StreamletEventKind::Equivocation,
+        }
+    }
+}
+
+/// True once `votes` strictly exceeds the fraction `quorum_numerator /
+/// quorum_denominator` of the `n` nodes a `NodeSetInfo` requires to notarize
+/// a block. Streamlet's usual two-thirds quorum is `quorum_numerator = 2,
+/// quorum_denominator = 3`. Compares `votes * quorum_denominator` against
+/// `quorum_numerator * n` rather than dividing first, so the check isn't
+/// skewed by integer-division rounding (dividing first made quorum
+/// unreachable for some small `n`).
+fn threshold_met(votes: usize, n: usize, quorum_numerator: u64, quorum_denominator: u64) -> bool {
+    votes as u64 * quorum_denominator > quorum_numerator * n as u64
+}
+
+/// Compact proof that a block was notarized: a bitmap of which `NodeSetInfo`
+/// indices voted for it, and a single BLS signature aggregating all of their
+/// votes. Verifying it is one pairing check, so gossiping this instead of
+/// each individual vote shrinks notarization messages from O(n) to O(1).
+#[derive(Debug, Clone)]
+struct NotarizationCertificate<T> {
+    pub block: Block<T>,
+    pub signer_bitmap: Vec<bool>,
+    pub aggregate_signature: BlsSignature,
+}
+
+impl<T> NotarizationCertificate<T>
+where
+    T: Serialize + Clone + PartialEq + Eq + std::fmt::Debug,
+{
+    /// Aggregates `signatures` (each paired with the `NodeSetInfo` index of
+    /// its signer) for `block` into a certificate over `n` possible signers.
+    fn aggregate(
+        block: &Block<T>,
+        signatures: &[(NodeID, BlsSignature)],
+        n: usize,
+    ) -> crypto::Result<Self> {
+        let mut signer_bitmap = vec![false; n];
+        for (id, _) in signatures {
+            signer_bitmap[*id] = true;
+        }
+        let sigs: Vec<&BlsSignature> = signatures.iter().map(|(_, sig)| sig).collect();
+        let aggregate_signature = crypto::aggregate_signatures(&sigs)?;
+        Ok(NotarizationCertificate {
+            block: block.clone(),
+            signer_bitmap,
+            aggregate_signature,
+        })
+    }
+
+    fn signer_count(&self) -> usize {
+        self.signer_bitmap.iter().filter(|signed| **signed).count()
+    }
+
+    /// Verifies the aggregate signature against the BLS public keys of the
+    /// signers named in `signer_bitmap`, and that they meet quorum.
+    fn verify(&self, node_set_info: &NodeSetInfo) -> crypto::Result<()> {
+        if !threshold_met(
+            self.signer_count(),
+            node_set_info.num_nodes(),
+            node_set_info.quorum_numerator,
+            node_set_info.quorum_denominator,
+        ) {
+            return Err(crypto::CryptoError::SignatureVerificationError);
+        }
+        let signers: Vec<&BlsPublicKey> = self
+            .signer_bitmap
+            .iter()
+            .enumerate()
+            .filter(|(_, signed)| **signed)
+            .map(|(id, _)| {
+                node_set_info
+                    .get_bls_public_key(id)
+                    .expect("signer bitmap index must be within the node set")
+            })
+            .collect();
+        crypto::fast_aggregate_verify(&signers, self.block.hash().as_bytes(), &self.aggregate_signature)
+    }
+}
+
+/// A registered event callback, optionally restricted to a subset of event kinds.
+struct Subscriber<T> {
+    filter: Option<HashSet<StreamletEventKind>>,
+    callback: Box<dyn FnMut(&StreamletEvent<T>)>,
+}
+
+/// Determines which node proposes a block for a given epoch. All honest nodes
+/// must agree on the schedule so that `message_is_valid_proposal` accepts the
+/// same leader everywhere.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LeaderSchedule {
+    /// `leader = epoch % num_nodes`.
+    RoundRobin,
+    /// `leader = SHA256(epoch) % num_nodes`, so the sequence is unpredictable
+    /// ahead of time but still deterministic and agreed by every node.
+    HashRotation,
+}
+
+impl Default for LeaderSchedule {
+    fn default() -> Self {
+        LeaderSchedule::RoundRobin
+    }
+}
+
+impl LeaderSchedule {
+    pub fn leader(&self, epoch: EpochNum, num_nodes: usize) -> NodeID {
+        assert!(num_nodes > 0, "a schedule needs at least one node to pick from");
+        let index = match self {
+            LeaderSchedule::RoundRobin => epoch % num_nodes as u64,
+            LeaderSchedule::HashRotation => {
+                let digest = Sha256::digest(epoch.to_le_bytes());
+                let seed = u64::from_le_bytes(digest[..8].try_into().unwrap());
+                seed % num_nodes as u64
+            }
+        };
+        index as NodeID
+    }
 }
 
 #[derive(Debug, Clone)]
 struct NodeSetInfo {
     node_pub_keys: Vec<PublicKey>,
+    bls_pub_keys: Vec<BlsPublicKey>,
+    leader_schedule: LeaderSchedule,
+    quorum_numerator: u64,
+    quorum_denominator: u64,
 }
 
 impl NodeSetInfo {
@@ -33,27 +274,115 @@ impl NodeSetInfo {
         self.node_pub_keys.get(id)
     }
 
+    pub fn get_bls_public_key(&self, id: NodeID) -> Option<&BlsPublicKey> {
+        self.bls_pub_keys.get(id)
+    }
+
     pub fn num_nodes(&self) -> usize {
         self.node_pub_keys.len()
     }
+
+    /// Builds the validator set from a `ChainSpec`'s authority list, in the
+    /// order that assigns each authority its `NodeID`.
+    fn from_spec(chain_spec: &ChainSpec) -> SpecResult<Self> {
+        let mut node_pub_keys = Vec::with_capacity(chain_spec.authorities.len());
+        let mut bls_pub_keys = Vec::with_capacity(chain_spec.authorities.len());
+        for (index, authority) in chain_spec.authorities.iter().enumerate() {
+            let public_key =
+                crypto::decode_public_key(&authority.public_key).context(spec::MalformedKeySnafu { index })?;
+            let bls_public_key =
+                BlsPublicKey::from_bytes(&authority.bls_public_key).context(spec::MalformedKeySnafu { index })?;
+            node_pub_keys.push(public_key);
+            bls_pub_keys.push(bls_public_key);
+        }
+        Ok(NodeSetInfo {
+            node_pub_keys,
+            bls_pub_keys,
+            leader_schedule: chain_spec.leader_schedule.clone(),
+            quorum_numerator: chain_spec.quorum_numerator,
+            quorum_denominator: chain_spec.quorum_denominator,
+        })
+    }
+}
+
+impl<T> Node<T, InMemoryBlockStore<T>>
+where
+    T: Serialize + Clone + PartialEq + Eq + std::fmt::Debug,
+{
+    /// Creates a new node initialized at the starting epoch number, with a
+    /// blockchain containing only the genesis block, backed by an in-memory
+    /// store. Use `with_store` for a durable, crash-recoverable store.
+    pub fn new(id: NodeID, keypair: Keypair, bls_keypair: BlsKeypair, node_set_info: NodeSetInfo) -> Self {
+        Self::with_store(id, keypair, bls_keypair, node_set_info, InMemoryBlockStore::new())
+    }
+
+    /// Boots a node from a shared `ChainSpec`: builds the validator set from
+    /// its authority list, checks that `keypair` and `bls_keypair`'s public
+    /// keys match the authority declared for `id`, and checks that this
+    /// binary's genesis block matches the one the spec commits to, so that
+    /// independently started processes all agree on the same genesis and
+    /// ordering instead of a `NodeSetInfo` assembled by hand.
+    pub fn from_spec(id: NodeID, keypair: Keypair, bls_keypair: BlsKeypair, chain_spec: &ChainSpec) -> SpecResult<Self> {
+        let node_set_info = NodeSetInfo::from_spec(chain_spec)?;
+
+        // Checked before the key comparisons below so a malformed spec is
+        // always reported as such, regardless of whether the caller's keys
+        // happen to match their declared slot.
+        let expected_genesis = HashOf::<Block<T>>::try_from_bytes(&chain_spec.genesis_hash).context(
+            spec::MalformedGenesisHashSnafu {
+                actual: chain_spec.genesis_hash.len(),
+            },
+        )?;
+        if expected_genesis != Block::<T>::genesis_block().hash() {
+            return spec::GenesisMismatchSnafu.fail();
+        }
+
+        let declared_key = node_set_info.get_public_key(id).context(spec::UnknownNodeSnafu {
+            id,
+            num_authorities: node_set_info.num_nodes(),
+        })?;
+        if &keypair.public != declared_key {
+            return spec::KeyMismatchSnafu { id }.fail();
+        }
+
+        let declared_bls_key = node_set_info
+            .get_bls_public_key(id)
+            .expect("bls and ed25519 authority keys have the same length by construction");
+        if bls_keypair.public.as_bytes() != declared_bls_key.as_bytes() {
+            return spec::KeyMismatchSnafu { id }.fail();
+        }
+
+        let mut node = Self::new(id, keypair, bls_keypair, node_set_info);
+        node.epoch_num = chain_spec.initial_epoch;
+        Ok(node)
+    }
 }
 
-impl<T> Node<T>
+impl<T, S> Node<T, S>
 where
     T: Serialize + Clone + PartialEq + Eq + std::fmt::Debug,
+    S: BlockStore<T> + Clone,
 {
-    /// Creates a new node initialized at the starting epoch number
-    /// with a blockchain containing only the genesis block
-    pub fn new(id: NodeID, keypair: Keypair, node_set_info: NodeSetInfo) -> Self {
+    /// Creates a new node initialized at the starting epoch number, with a
+    /// blockchain containing only the genesis block, persisted to `store`.
+    pub fn with_store(
+        id: NodeID,
+        keypair: Keypair,
+        bls_keypair: BlsKeypair,
+        node_set_info: NodeSetInfo,
+        store: S,
+    ) -> Self {
         assert!(
             &keypair.public
                 == node_set_info
                     .get_public_key(id)
                     .expect("There should be a key associated with this node id")
         );
-        let initial_chain = BlockChain::new();
+        let initial_chain = BlockChain::with_store(store);
+        let finalized_height = initial_chain.block_height();
+        let finalized_blocks = initial_chain.blocks().to_vec();
         let mut chains = BTreeMap::new();
-        let votes: HashMap<HashOf<Block<T>>, (u64, HashSet<NodeID>)> = HashMap::new();
+        let votes: HashMap<HashOf<Block<T>>, VoteTally> = HashMap::new();
         chains.insert(initial_chain.block_height(), vec![initial_chain]);
         Node {
             epoch_num: INITIAL_EPOCH,
@@ -61,18 +390,91 @@ where
             node_set_info,
             chains: chains,
             keypair,
+            bls_keypair,
             votes,
             proposal_recieved: false,
+            finalized_height,
+            finalized_blocks,
+            subscribers: Vec::new(),
+            votes_by_epoch_voter: HashMap::new(),
+            equivocators: HashSet::new(),
+        }
+    }
+
+    /// Reconstructs a node from `store` after a crash: the persisted
+    /// finalized prefix plus any notarized-but-unfinalized tail become the
+    /// node's sole chain, and the epoch number resumes after the last
+    /// recovered block so the node doesn't re-propose an epoch it already
+    /// participated in.
+    pub fn recover(
+        id: NodeID,
+        keypair: Keypair,
+        bls_keypair: BlsKeypair,
+        node_set_info: NodeSetInfo,
+        store: S,
+    ) -> StoreResult<Self> {
+        assert!(
+            &keypair.public
+                == node_set_info
+                    .get_public_key(id)
+                    .expect("There should be a key associated with this node id")
+        );
+        let initial_chain = BlockChain::recover(store)?;
+        let blocks = initial_chain.blocks();
+        let finalized_height = finalized_height(blocks);
+        let finalized_blocks = blocks[..finalized_height].to_vec();
+        let epoch_num = blocks.last().map(|b| b.epoch + 1).unwrap_or(INITIAL_EPOCH);
+
+        let mut chains = BTreeMap::new();
+        let votes: HashMap<HashOf<Block<T>>, VoteTally> = HashMap::new();
+        chains.insert(initial_chain.block_height(), vec![initial_chain]);
+        Ok(Node {
+            epoch_num,
+            node_id: id,
+            node_set_info,
+            chains: chains,
+            keypair,
+            bls_keypair,
+            votes,
+            proposal_recieved: false,
+            finalized_height,
+            finalized_blocks,
+            subscribers: Vec::new(),
+            votes_by_epoch_voter: HashMap::new(),
+            equivocators: HashSet::new(),
+        })
+    }
+
+    /// The prefix of the chain that is irreversibly finalized, in order from genesis.
+    pub fn finalized_chain(&self) -> &[Block<T>] {
+        &self.finalized_blocks
+    }
+
+    /// Registers a callback to be invoked for every `StreamletEvent` the node
+    /// emits, optionally restricted to a subset of event kinds.
+    pub fn subscribe(
+        &mut self,
+        filter: Option<HashSet<StreamletEventKind>>,
+        callback: Box<dyn FnMut(&StreamletEvent<T>)>,
+    ) {
+        self.subscribers.push(Subscriber { filter, callback });
+    }
+
+    fn emit(&mut self, event: StreamletEvent<T>) {
+        let kind = event.kind();
+        for subscriber in self.subscribers.iter_mut() {
+            if subscriber.filter.as_ref().map_or(true, |kinds| kinds.contains(&kind)) {
+                (subscriber.callback)(&event);
+            }
         }
     }
     /// Returns true if the Node is the leader of the current epoch, and false otherwise.
     pub fn is_leader(&self) -> bool {
-        self.get_leader() == self.node_id
+        self.get_leader(self.epoch_num) == self.node_id
     }
 
     /// Advances the Node to the next epoch, returning the new epoch number.
     pub fn advance_epoch(&mut self) -> EpochNum {
-        dbg!(&self.chains);
         self.epoch_num += 1;
         self.epoch_num
     }
@@ -83,80 +485,181 @@ where
         let longest_chain = self.peek_longest_chain();
         let prev_hash = longest_chain.get_latest_block_hash();
         let block = Block::new(payload, prev_hash, epoch);
+        let bls_sig = self.bls_keypair.sign(block.hash().as_bytes());
         let notarized_block = Signed::new(block, &self.keypair);
 
-        let msg = Message {
+        Message::Vote(VoteMessage {
             recipients: self.every_node_except_me(),
             vote: notarized_block,
             voter: self.node_id,
-        };
-
-        msg
+            bls_sig,
+        })
     }
 
-    /// Handle an incoming messages, checking for valid votes and proposals
+    /// Handle an incoming message, checking for valid votes, proposals and certificates.
     pub fn handle_message(&mut self, msg: &Message<T>) -> Vec<Message<T>> {
+        match msg {
+            Message::Vote(vote_msg) => self.handle_vote(vote_msg),
+            Message::Certificate(cert_msg) => self.handle_certificate(cert_msg),
+        }
+    }
+
+    fn handle_vote(&mut self, vote_msg: &VoteMessage<T>) -> Vec<Message<T>> {
         let mut messages = vec![];
-        let n = self.node_set_info.num_nodes();
         // First, validate the vote
-        if self.validate_vote(msg.voter, &msg.vote) {
-            // TODO simply increment vote counter if we've seen this block
-            // TODO maybe networking layer should be responsible for not sending
-            // duplicate messages
-            let new_block = msg.vote.get_data();
-            let hash = new_block.hash();
-            let votes = self.votes.entry(hash).or_insert((0, HashSet::new()));
-            if (*votes).1.insert(msg.voter) {
-                votes.0 += 1;
-
-                if (votes.0 as usize) > (2 * n) / 3 + 1 {
-                    self.try_append_block(new_block)
-                }
+        if !self.validate_vote(vote_msg.voter, &vote_msg.vote) {
+            return messages;
+        }
 
-                let echo_message = Message {
-                    recipients: self.every_node_except_me(),
-                    vote: msg.vote.clone(),
-                    voter: msg.voter,
-                };
+        let new_block = vote_msg.vote.get_data().clone();
+        let hash = new_block.hash();
 
-                messages.push(echo_message);
+        if let Some(proof) = self.check_equivocation(vote_msg.voter, new_block.epoch, &vote_msg.vote) {
+            self.emit(StreamletEvent::Equivocation(proof));
+        }
 
-                if self.message_is_valid_proposal(msg) {
-                    // If valid proposal is recieved, vote for it and forward along
-                    self.proposal_recieved = true;
+        if self.equivocators.contains(&vote_msg.voter) {
+            return messages;
+        }
 
-                    let vote_message = Message {
-                        recipients: self.every_node_except_me(),
-                        vote: Signed::new(new_block.clone(), &self.keypair),
-                        voter: self.node_id,
-                    };
+        let n = self.node_set_info.num_nodes();
+        let quorum_numerator = self.node_set_info.quorum_numerator;
+        let quorum_denominator = self.node_set_info.quorum_denominator;
+        // TODO maybe networking layer should be responsible for not sending
+        // duplicate messages
+        let (is_new_vote, just_reached_threshold, votes_count, signatures) = {
+            let tally = self.votes.entry(hash.clone()).or_insert_with(VoteTally::default);
+            if tally.certified {
+                // Already certified and broadcast as a certificate; a late
+                // raw vote needs no further action.
+                return messages;
+            }
 
-                    messages.push(vote_message);
+            let is_new_vote = tally.voters.insert(vote_msg.voter);
+            if is_new_vote {
+                tally.signatures.push((vote_msg.voter, vote_msg.bls_sig.clone()));
+            }
+            let just_reached_threshold =
+                is_new_vote && threshold_met(tally.voters.len(), n, quorum_numerator, quorum_denominator);
+            tally.certified = tally.certified || just_reached_threshold;
+
+            (
+                is_new_vote,
+                just_reached_threshold,
+                tally.voters.len() as u64,
+                tally.signatures.clone(),
+            )
+        };
+
+        if just_reached_threshold {
+            match NotarizationCertificate::aggregate(&new_block, &signatures, n) {
+                Ok(certificate) => {
+                    self.emit(StreamletEvent::BlockNotarized {
+                        block_hash: hash.clone(),
+                        votes: votes_count,
+                    });
+                    // TODO stop swallowing store errors once there's somewhere to send them
+                    let _finalized = self.try_append_block(&new_block);
+
+                    messages.push(Message::Certificate(CertificateMessage {
+                        recipients: self.every_node_except_me(),
+                        certificate,
+                    }));
                 }
+                // TODO surface aggregation errors once there's somewhere to send them
+                Err(_) => (),
             }
+        } else if is_new_vote {
+            messages.push(Message::Vote(VoteMessage {
+                recipients: self.every_node_except_me(),
+                vote: vote_msg.vote.clone(),
+                voter: vote_msg.voter,
+                bls_sig: vote_msg.bls_sig.clone(),
+            }));
         }
+
+        if is_new_vote && self.vote_message_is_valid_proposal(vote_msg) {
+            // If valid proposal is recieved, vote for it and forward along
+            self.proposal_recieved = true;
+            self.emit(StreamletEvent::ProposalReceived {
+                epoch: new_block.epoch,
+                block_hash: hash.clone(),
+            });
+
+            messages.push(Message::Vote(VoteMessage {
+                recipients: self.every_node_except_me(),
+                vote: Signed::new(new_block.clone(), &self.keypair),
+                voter: self.node_id,
+                bls_sig: self.bls_keypair.sign(hash.as_bytes()),
+            }));
+        }
+
         messages
     }
 
-    /// A message is a valid proposal iff:
+    /// Adopts a notarization certificate received from a peer: verifies it,
+    /// applies it exactly once, and forwards it on (still O(1) per hop,
+    /// unlike re-gossiping each of the underlying votes).
+    fn handle_certificate(&mut self, cert_msg: &CertificateMessage<T>) -> Vec<Message<T>> {
+        let mut messages = vec![];
+        if cert_msg.certificate.verify(&self.node_set_info).is_err() {
+            return messages;
+        }
+
+        let hash = cert_msg.certificate.block.hash();
+        let already_certified = {
+            let tally = self.votes.entry(hash.clone()).or_insert_with(VoteTally::default);
+            let already_certified = tally.certified;
+            tally.certified = true;
+            already_certified
+        };
+        if already_certified {
+            return messages;
+        }
+
+        self.emit(StreamletEvent::BlockNotarized {
+            block_hash: hash.clone(),
+            votes: cert_msg.certificate.signer_count() as u64,
+        });
+        // TODO stop swallowing store errors once there's somewhere to send them
+        let _finalized = self.try_append_block(&cert_msg.certificate.block);
+
+        messages.push(Message::Certificate(CertificateMessage {
+            recipients: self.every_node_except_me(),
+            certificate: cert_msg.certificate.clone(),
+        }));
+        messages
+    }
+
+    /// A vote is a valid proposal iff:
     /// 1) The epoch number of the block matches the current epoch
     /// 2) The signer of the block is the leader of the current epoch
     /// 3) The signature is valid
     // TODO check signature here too?
-    fn message_is_valid_proposal(&self, msg: &Message<T>) -> bool {
-        let block = msg.vote.get_data();
-        let valid_vote = self.validate_vote(msg.voter, &msg.vote);
-        return block.epoch == self.epoch_num && msg.voter == self.get_leader() && valid_vote;
+    fn vote_message_is_valid_proposal(&self, vote_msg: &VoteMessage<T>) -> bool {
+        let block = vote_msg.vote.get_data();
+        let valid_vote = self.validate_vote(vote_msg.voter, &vote_msg.vote);
+        block.epoch == self.epoch_num && vote_msg.voter == self.get_leader(block.epoch) && valid_vote
     }
 
-    fn get_leader(&self) -> NodeID {
-        return 0;
+    /// The leader for `epoch`, per the node set's `LeaderSchedule`. Takes the
+    /// epoch explicitly (rather than always using `self.epoch_num`) so a block
+    /// is checked against the leader scheduled for the epoch it carries.
+    /// `vote_message_is_valid_proposal` also requires that epoch to be the
+    /// current one, so a proposal for a past epoch is not accepted.
+    fn get_leader(&self, epoch: EpochNum) -> NodeID {
+        self.node_set_info
+            .leader_schedule
+            .leader(epoch, self.node_set_info.num_nodes())
     }
 
-    fn try_append_block(&mut self, new_block: &Block<T>) {
+    fn try_append_block(&mut self, new_block: &Block<T>) -> StoreResult<Option<BlockFinalized<T>>> {
         // TODO remove unwraps
         let hash = new_block.prev_hash.as_ref().unwrap();
-        // Try to add to one of our longest chains
+        // Try to add to one of our longest chains. A block extending a
+        // shorter, already-overtaken chain isn't adopted here and is
+        // dropped rather than tracked as a candidate that could retake the
+        // lead later.
         let mut blockchain_entry = self.chains.last_entry().unwrap();
         let blockchains = blockchain_entry.get_mut();
         let mut idx = None;
@@ -168,13 +671,51 @@ where
         }
 
         if let Some(idx) = idx {
-            let mut chain = blockchains.remove(idx);
-            chain.add_block(new_block);
-            self.chains.insert(chain.block_height(), vec![chain]);
+            // Extend a fork of the matched chain rather than consuming it in
+            // place, so the original stays tracked in `chains` at its own
+            // height alongside the new branch; being shorter, it is no longer
+            // searched above. Forking gives the new branch its own store, so
+            // the two stop sharing writes the moment they diverge.
+            let mut chain = blockchains[idx].fork()?;
+            chain.add_block(new_block)?;
+            let finalized = self.check_finalization(&chain);
+            self.chains.entry(chain.block_height()).or_insert_with(Vec::new).push(chain);
+            return Ok(finalized);
         }
+        Ok(None)
+    }
+
+    /// Applies Streamlet's finalization rule to `chain`: if the last three blocks
+    /// occupy consecutive heights with consecutive epochs `e, e+1, e+2`, the block
+    /// at epoch `e+1` (and everything before it) becomes final. The genesis block
+    /// is epoch 0, so it participates in the first such window. The finalized
+    /// pointer only ever advances, and only along the already-finalized prefix:
+    /// a competing fork that reaches a greater height is not enough on its own,
+    /// since it need not descend from the blocks this chain already finalized.
+    fn check_finalization(&mut self, chain: &BlockChain<T, S>) -> Option<BlockFinalized<T>> {
+        let blocks = chain.blocks();
+        let new_finalized_height = finalized_height(blocks);
+        if new_finalized_height <= self.finalized_height {
+            return None;
+        }
+        if blocks[..self.finalized_height] != self.finalized_blocks[..] {
+            return None;
+        }
+
+        let finalized_block = blocks[new_finalized_height - 1].clone();
+        self.finalized_blocks
+            .extend(blocks[self.finalized_height..new_finalized_height].iter().cloned());
+        self.finalized_height = new_finalized_height;
+
+        let finalized = BlockFinalized {
+            block_hash: finalized_block.hash(),
+            height: new_finalized_height,
+        };
+        self.emit(StreamletEvent::BlockFinalized(finalized.clone()));
+        Some(finalized)
     }
 
-    fn peek_longest_chain(&self) -> BlockChain<T> {
+    fn peek_longest_chain(&self) -> BlockChain<T, S> {
         let blockchains = self.chains.last_key_value().unwrap();
         // Just pick the first one, maybe random is better?
         (blockchains.1)[0].clone()
@@ -191,23 +732,87 @@ where
         let pk = self.node_set_info.get_public_key(voter).unwrap();
         vote.verify(&pk).is_ok()
     }
+
+    /// Records `voter`'s first signed block for `epoch`. If `voter` already
+    /// signed a *different* block for this epoch, marks them as an
+    /// equivocator (so `handle_message` ignores further votes from them) and
+    /// returns proof of the misbehavior. A voter already known to be an
+    /// equivocator is reported once, not on every further conflicting vote.
+    fn check_equivocation(
+        &mut self,
+        voter: NodeID,
+        epoch: EpochNum,
+        vote: &Signed<Block<T>>,
+    ) -> Option<EquivocationProof<T>> {
+        if self.equivocators.contains(&voter) {
+            return None;
+        }
+        match self.votes_by_epoch_voter.entry((epoch, voter)) {
+            std::collections::hash_map::Entry::Occupied(entry) => {
+                let first = entry.get();
+                if first.get_data().hash() == vote.get_data().hash() {
+                    None
+                } else {
+                    let proof = EquivocationProof {
+                        epoch,
+                        voter,
+                        first: first.clone(),
+                        second: vote.clone(),
+                    };
+                    self.equivocators.insert(voter);
+                    Some(proof)
+                }
+            }
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                entry.insert(vote.clone());
+                None
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use rand::{CryptoRng, Rng};
+    use std::cell::RefCell;
+    use std::rc::Rc;
 
-    fn simulate_protocol<R: Rng + CryptoRng>(num_nodes: usize, rounds: usize, rand: &mut R) {
+    /// Runs `rounds` epochs of the protocol among `num_nodes` freshly generated
+    /// nodes, with one always-online leader per epoch, rotating round-robin. If `subscriber`
+    /// is given, it's registered on node 0 (with the given filter) before the
+    /// run starts, so a test can assert on the events a real run produces.
+    fn simulate_protocol<R: Rng + CryptoRng>(
+        num_nodes: usize,
+        rounds: usize,
+        rand: &mut R,
+        subscriber: Option<(Option<HashSet<StreamletEventKind>>, Rc<RefCell<Vec<StreamletEventKind>>>)>,
+    ) -> Vec<Node<u64>> {
         let mut keypairs: Vec<Keypair> = (0..num_nodes).map(|_| Keypair::generate(rand)).collect();
         let node_pub_keys: Vec<PublicKey> = keypairs.iter().map(|kp| kp.public).collect();
-        let node_info = NodeSetInfo { node_pub_keys };
+        let mut bls_keypairs: Vec<BlsKeypair> = (0..num_nodes).map(|_| BlsKeypair::generate(rand)).collect();
+        let bls_pub_keys: Vec<BlsPublicKey> = bls_keypairs.iter().map(|kp| kp.public.clone()).collect();
+        let node_info = NodeSetInfo {
+            node_pub_keys,
+            bls_pub_keys,
+            leader_schedule: LeaderSchedule::RoundRobin,
+            quorum_numerator: 2,
+            quorum_denominator: 3,
+        };
         // TODO enumerate
         let mut nodes: Vec<Node<u64>> = (0..num_nodes)
             .zip(keypairs.drain(..))
-            .map(|(id, key)| Node::new(id, key, node_info.clone()))
+            .zip(bls_keypairs.drain(..))
+            .map(|((id, key), bls_key)| Node::new(id, key, bls_key, node_info.clone()))
             .collect();
 
+        if let Some((filter, observed)) = subscriber {
+            nodes[0].subscribe(
+                filter,
+                Box::new(move |event| observed.borrow_mut().push(event.kind())),
+            );
+        }
+
         let mut network_messages = Vec::new();
         for _ in (0..rounds) {
             for node in nodes.iter() {
@@ -217,7 +822,7 @@ mod test {
             }
             while !network_messages.is_empty() {
                 let msg = network_messages.pop().unwrap();
-                for recipient in msg.recipients.clone() {
+                for recipient in msg.recipients().clone() {
                     let new_messages = nodes[recipient].handle_message(&msg);
                     network_messages.extend(new_messages);
                 }
@@ -226,6 +831,8 @@ mod test {
                 node.advance_epoch();
             }
         }
+
+        nodes
     }
 
     #[test]
@@ -233,6 +840,205 @@ mod test {
         let mut rng = rand::thread_rng();
         let n_nodes = 5;
         let n_rounds = 10;
-        simulate_protocol(n_nodes, n_rounds, &mut rng);
+        simulate_protocol(n_nodes, n_rounds, &mut rng, None);
+    }
+
+    #[test]
+    fn finalizes_after_three_consecutive_epochs() {
+        let mut rng = rand::thread_rng();
+        let n_nodes = 4;
+        let n_rounds = 6;
+        let nodes = simulate_protocol(n_nodes, n_rounds, &mut rng, None);
+
+        // With exactly one always-online leader proposing in every epoch, the chain
+        // never forks, so every honest node should finalize the same prefix.
+        assert!(nodes.iter().all(|node| node.finalized_chain().len() > 1));
+    }
+
+    #[test]
+    fn subscriber_observes_full_event_lifecycle() {
+        let mut rng = rand::thread_rng();
+        let observed = Rc::new(RefCell::new(Vec::new()));
+        simulate_protocol(4, 6, &mut rng, Some((None, observed.clone())));
+
+        let observed = observed.borrow();
+        assert!(observed.contains(&StreamletEventKind::ProposalReceived));
+        assert!(observed.contains(&StreamletEventKind::BlockNotarized));
+        assert!(observed.contains(&StreamletEventKind::BlockFinalized));
+    }
+
+    #[test]
+    fn subscriber_filter_excludes_other_kinds() {
+        let mut rng = rand::thread_rng();
+        let observed = Rc::new(RefCell::new(Vec::new()));
+        let mut only_finalized = HashSet::new();
+        only_finalized.insert(StreamletEventKind::BlockFinalized);
+        simulate_protocol(4, 6, &mut rng, Some((Some(only_finalized), observed.clone())));
+
+        let observed = observed.borrow();
+        assert!(!observed.is_empty());
+        assert!(observed.iter().all(|kind| *kind == StreamletEventKind::BlockFinalized));
+    }
+
+    #[test]
+    fn equivocating_voter_is_detected_and_ignored() {
+        let mut rng = rand::thread_rng();
+        let own_keypair = Keypair::generate(&mut rng);
+        let own_bls_keypair = BlsKeypair::generate(&mut rng);
+        let equivocator_keypair = Keypair::generate(&mut rng);
+        let equivocator_bls_keypair = BlsKeypair::generate(&mut rng);
+        let equivocator: NodeID = 1;
+        let node_info = NodeSetInfo {
+            node_pub_keys: vec![own_keypair.public, equivocator_keypair.public],
+            bls_pub_keys: vec![own_bls_keypair.public.clone(), equivocator_bls_keypair.public.clone()],
+            leader_schedule: LeaderSchedule::RoundRobin,
+            quorum_numerator: 2,
+            quorum_denominator: 3,
+        };
+        let mut node: Node<u64> = Node::new(0, own_keypair, own_bls_keypair, node_info);
+
+        let observed: Rc<RefCell<Vec<StreamletEventKind>>> = Rc::new(RefCell::new(Vec::new()));
+        let observed_handle = observed.clone();
+        node.subscribe(None, Box::new(move |event| observed_handle.borrow_mut().push(event.kind())));
+
+        let genesis_hash = HashOf::new(&Block::<u64>::genesis_block());
+        let block_a = Block::new(1u64, genesis_hash.clone(), 1);
+        let block_b = Block::new(2u64, genesis_hash, 1);
+
+        let vote_a = Message::Vote(VoteMessage {
+            recipients: HashSet::new(),
+            bls_sig: equivocator_bls_keypair.sign(block_a.hash().as_bytes()),
+            vote: Signed::new(block_a.clone(), &equivocator_keypair),
+            voter: equivocator,
+        });
+        let vote_b = Message::Vote(VoteMessage {
+            recipients: HashSet::new(),
+            bls_sig: equivocator_bls_keypair.sign(block_b.hash().as_bytes()),
+            vote: Signed::new(block_b, &equivocator_keypair),
+            voter: equivocator,
+        });
+
+        node.handle_message(&vote_a);
+        assert!(!observed.borrow().contains(&StreamletEventKind::Equivocation));
+
+        node.handle_message(&vote_b);
+        assert!(observed.borrow().contains(&StreamletEventKind::Equivocation));
+
+        // A later, third different vote from the same node is silently
+        // dropped, not re-reported, since they're already known to be
+        // misbehaving.
+        let before = observed.borrow().len();
+        let block_c = Block::new(3u64, HashOf::new(&block_a), 1);
+        let vote_c = Message::Vote(VoteMessage {
+            recipients: HashSet::new(),
+            bls_sig: equivocator_bls_keypair.sign(block_c.hash().as_bytes()),
+            vote: Signed::new(block_c, &equivocator_keypair),
+            voter: equivocator,
+        });
+        node.handle_message(&vote_c);
+        assert_eq!(observed.borrow().len(), before);
+    }
+
+    #[test]
+    fn notarization_certificate_aggregates_and_verifies() {
+        let mut rng = rand::thread_rng();
+        let keypairs: Vec<Keypair> = (0..4).map(|_| Keypair::generate(&mut rng)).collect();
+        let node_pub_keys: Vec<PublicKey> = keypairs.iter().map(|kp| kp.public).collect();
+        let bls_keypairs: Vec<BlsKeypair> = (0..4).map(|_| BlsKeypair::generate(&mut rng)).collect();
+        let bls_pub_keys: Vec<BlsPublicKey> = bls_keypairs.iter().map(|kp| kp.public.clone()).collect();
+        let node_info = NodeSetInfo {
+            node_pub_keys,
+            bls_pub_keys,
+            leader_schedule: LeaderSchedule::RoundRobin,
+            quorum_numerator: 2,
+            quorum_denominator: 3,
+        };
+
+        let block = Block::new(7u64, HashOf::new(&Block::<u64>::genesis_block()), 1);
+        let signatures: Vec<(NodeID, BlsSignature)> = bls_keypairs
+            .iter()
+            .enumerate()
+            .map(|(id, kp)| (id, kp.sign(block.hash().as_bytes())))
+            .collect();
+
+        let certificate = NotarizationCertificate::aggregate(&block, &signatures, node_info.num_nodes())
+            .expect("aggregating valid signatures should succeed");
+        assert!(certificate.verify(&node_info).is_ok());
+
+        // A certificate whose aggregate signature doesn't match its block
+        // fails the pairing check even though the signer bitmap still meets
+        // quorum.
+        let mut tampered = certificate.clone();
+        tampered.block = Block::new(999u64, HashOf::new(&Block::<u64>::genesis_block()), 1);
+        assert!(tampered.verify(&node_info).is_err());
+    }
+
+    #[test]
+    fn leader_schedules_rotate_and_agree() {
+        let num_nodes = 5;
+
+        // Round-robin is just epoch % num_nodes, and should actually rotate
+        // rather than pinning a single leader forever.
+        let round_robin_leaders: HashSet<NodeID> = (0..num_nodes as u64)
+            .map(|epoch| LeaderSchedule::RoundRobin.leader(epoch, num_nodes))
+            .collect();
+        assert_eq!(round_robin_leaders.len(), num_nodes);
+
+        // Hash rotation must be a pure function of the epoch, so every node
+        // computing it independently agrees on the leader.
+        for epoch in 0..10 {
+            let a = LeaderSchedule::HashRotation.leader(epoch, num_nodes);
+            let b = LeaderSchedule::HashRotation.leader(epoch, num_nodes);
+            assert_eq!(a, b);
+            assert!(a < num_nodes);
+        }
+    }
+
+    #[test]
+    fn node_boots_from_chain_spec_and_validates_slot() {
+        let mut rng = rand::thread_rng();
+        // Keep the raw key bytes around (neither `Keypair` nor `BlsKeypair`
+        // is `Clone`) so slot 1's ed25519 key can be reused on its own below.
+        let keypair_bytes: Vec<[u8; 64]> = (0..3).map(|_| Keypair::generate(&mut rng).to_bytes()).collect();
+        let keypairs: Vec<Keypair> = keypair_bytes.iter().map(|b| Keypair::from_bytes(b).unwrap()).collect();
+        let bls_keypairs: Vec<BlsKeypair> = (0..3).map(|_| BlsKeypair::generate(&mut rng)).collect();
+        let authorities = keypairs
+            .iter()
+            .zip(bls_keypairs.iter())
+            .map(|(kp, bls_kp)| crate::spec::AuthoritySpec {
+                public_key: crypto::encode_public_key(&kp.public),
+                bls_public_key: bls_kp.public.as_bytes(),
+            })
+            .collect();
+        let chain_spec = crate::spec::ChainSpec {
+            authorities,
+            initial_epoch: 5,
+            quorum_numerator: 2,
+            quorum_denominator: 3,
+            leader_schedule: LeaderSchedule::RoundRobin,
+            genesis_hash: Block::<u64>::genesis_block().hash().as_bytes().to_vec(),
+        };
+
+        let node = Node::<u64>::from_spec(1, keypairs.into_iter().nth(1).unwrap(), bls_keypairs.into_iter().nth(1).unwrap(), &chain_spec)
+            .expect("a keypair matching its declared slot should boot successfully");
+        assert_eq!(node.epoch_num, 5);
+
+        let mismatched_keypair = Keypair::generate(&mut rng);
+        let mismatched_bls_keypair = BlsKeypair::generate(&mut rng);
+        let err = Node::<u64>::from_spec(1, mismatched_keypair, mismatched_bls_keypair, &chain_spec).unwrap_err();
+        assert!(matches!(err, spec::SpecError::KeyMismatch { id: 1 }));
+
+        // The ed25519 key matches slot 1, but its BLS key doesn't: this must
+        // be rejected too, not just checked for the ed25519 half.
+        let slot_one_keypair = Keypair::from_bytes(&keypair_bytes[1]).unwrap();
+        let err = Node::<u64>::from_spec(1, slot_one_keypair, BlsKeypair::generate(&mut rng), &chain_spec).unwrap_err();
+        assert!(matches!(err, spec::SpecError::KeyMismatch { id: 1 }));
+
+        let mut short_genesis_spec = chain_spec.clone();
+        short_genesis_spec.genesis_hash = vec![0u8; 4];
+        let slot_zero_keypair = Keypair::from_bytes(&keypair_bytes[0]).unwrap();
+        let err = Node::<u64>::from_spec(0, slot_zero_keypair, BlsKeypair::generate(&mut rng), &short_genesis_spec)
+            .unwrap_err();
+        assert!(matches!(err, spec::SpecError::MalformedGenesisHash { actual: 4 }));
     }
 }
@@ -0,0 +1,111 @@
+use crate::blockchain::EpochNum;
+use crate::crypto::CryptoError;
+use crate::node::{LeaderSchedule, NodeID};
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+use std::path::Path;
+
+pub type SpecResult<T, E = SpecError> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+pub enum SpecError {
+    #[snafu(display("could not read chain spec file: {}", source))]
+    Read { source: std::io::Error },
+    #[snafu(display("could not parse chain spec as JSON: {}", source))]
+    ParseJson { source: serde_json::Error },
+    #[snafu(display("could not parse chain spec as TOML: {}", source))]
+    ParseToml { source: toml::de::Error },
+    #[snafu(display("chain spec has an unrecognized extension: {}", extension))]
+    UnknownFormat { extension: String },
+    #[snafu(display("authority {} has a malformed public key: {}", index, source))]
+    MalformedKey { index: usize, source: CryptoError },
+    #[snafu(display("node id {} is not among the {} declared authorities", id, num_authorities))]
+    UnknownNode { id: NodeID, num_authorities: usize },
+    #[snafu(display("this node's keypair does not match the authority declared for id {}", id))]
+    KeyMismatch { id: NodeID },
+    #[snafu(display("chain spec genesis_hash must be 32 bytes, got {}", actual))]
+    MalformedGenesisHash { actual: usize },
+    #[snafu(display("this binary's genesis block does not match the one declared in the chain spec"))]
+    GenesisMismatch,
+}
+
+/// One validator's public keys, in the order that assigns it its `NodeID`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthoritySpec {
+    /// Ed25519 public key bytes, as produced by `crypto::encode_public_key`.
+    pub public_key: Vec<u8>,
+    /// BLS12-381 public key bytes, as produced by `BlsPublicKey::as_bytes`.
+    pub bls_public_key: Vec<u8>,
+}
+
+/// The shared configuration every node in a deployment boots from: the
+/// ordered validator set, the notarization quorum, the leader-election
+/// strategy, and the genesis block every chain must start from. Loading this
+/// from a single file means independently started processes agree on all of
+/// it, rather than each assembling a `NodeSetInfo` by hand the way tests do.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainSpec {
+    pub authorities: Vec<AuthoritySpec>,
+    pub initial_epoch: EpochNum,
+    /// A block is notarized once more than the fraction `quorum_numerator /
+    /// quorum_denominator` of the authorities have voted for it. Streamlet's
+    /// usual quorum is two-thirds, i.e. `quorum_numerator = 2, quorum_denominator = 3`.
+    pub quorum_numerator: u64,
+    pub quorum_denominator: u64,
+    pub leader_schedule: LeaderSchedule,
+    /// The digest of the genesis `Block`, as produced by `HashOf::as_bytes`.
+    pub genesis_hash: Vec<u8>,
+}
+
+impl ChainSpec {
+    /// Loads a chain spec from `path`, parsed as TOML or JSON according to
+    /// its extension (JSON if there is none).
+    pub fn load<P: AsRef<Path>>(path: P) -> SpecResult<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).context(ReadSnafu)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&contents).context(ParseTomlSnafu),
+            Some("json") | None => serde_json::from_str(&contents).context(ParseJsonSnafu),
+            Some(other) => UnknownFormatSnafu {
+                extension: other.to_string(),
+            }
+            .fail(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn loads_chain_spec_from_json() {
+        let json = r#"{
+            "authorities": [
+                {"public_key": [1, 2, 3], "bls_public_key": [4, 5, 6]},
+                {"public_key": [7, 8, 9], "bls_public_key": [10, 11, 12]}
+            ],
+            "initial_epoch": 3,
+            "quorum_numerator": 2,
+            "quorum_denominator": 3,
+            "leader_schedule": "HashRotation",
+            "genesis_hash": [0, 0, 0]
+        }"#;
+
+        let spec: ChainSpec = serde_json::from_str(json).expect("valid chain spec JSON should parse");
+        assert_eq!(spec.authorities.len(), 2);
+        assert_eq!(spec.initial_epoch, 3);
+        assert_eq!(spec.leader_schedule, LeaderSchedule::HashRotation);
+    }
+
+    #[test]
+    fn unrecognized_extension_is_rejected() {
+        let path = std::env::temp_dir().join("streamlet_rs_chain_spec_test.yaml");
+        std::fs::write(&path, "authorities: []").unwrap();
+
+        let err = ChainSpec::load(&path).unwrap_err();
+        assert!(matches!(err, SpecError::UnknownFormat { .. }));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
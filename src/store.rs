@@ -0,0 +1,307 @@
+use crate::blockchain::{finalized_height, Block, BlockHeight};
+use crate::crypto::HashOf;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use snafu::{ResultExt, Snafu};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+pub type StoreResult<T, E = StoreError> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+pub enum StoreError {
+    #[snafu(display("sqlite error: {}", source))]
+    Sqlite { source: rusqlite::Error },
+    #[snafu(display("stored block payload could not be decoded: {}", source))]
+    Decode { source: Box<bincode::ErrorKind> },
+}
+
+/// Durable storage for the blocks of a single chain. `BlockChain` writes
+/// through to it on every append, so a `Node` can be reconstructed after a
+/// crash instead of losing everything held only in its in-memory `Vec`.
+pub trait BlockStore<T> {
+    fn append(&mut self, block: &Block<T>) -> StoreResult<()>;
+    fn get_by_height(&self, height: BlockHeight) -> StoreResult<Option<Block<T>>>;
+    fn latest_hash(&self) -> StoreResult<Option<HashOf<Block<T>>>>;
+    fn load_finalized_prefix(&self) -> StoreResult<Vec<Block<T>>>;
+
+    /// Creates a fresh, independent store pre-populated with `blocks`. Used
+    /// by `BlockChain::fork` when a competing tip is about to diverge from
+    /// this chain, so the two branches stop sharing writes into the same
+    /// height-keyed store (which would otherwise interleave and corrupt
+    /// each other's blocks on `recover`).
+    fn forked(&self, blocks: &[Block<T>]) -> StoreResult<Self>
+    where
+        Self: Sized;
+}
+
+/// In-memory `BlockStore`, used by tests and anywhere durability isn't
+/// needed. Cloning shares the same underlying blocks, mirroring how cloning
+/// a `SqliteBlockStore` shares the same connection; use `forked` instead of
+/// `clone` when the copy is going to diverge (see `BlockChain::fork`).
+#[derive(Clone)]
+pub struct InMemoryBlockStore<T> {
+    blocks: Rc<RefCell<BTreeMap<BlockHeight, Block<T>>>>,
+}
+
+impl<T> std::fmt::Debug for InMemoryBlockStore<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InMemoryBlockStore").finish()
+    }
+}
+
+impl<T> InMemoryBlockStore<T> {
+    pub fn new() -> Self {
+        InMemoryBlockStore {
+            blocks: Rc::new(RefCell::new(BTreeMap::new())),
+        }
+    }
+}
+
+impl<T> Default for InMemoryBlockStore<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> BlockStore<T> for InMemoryBlockStore<T>
+where
+    T: Serialize + Clone + PartialEq + Eq + std::fmt::Debug,
+{
+    fn append(&mut self, block: &Block<T>) -> StoreResult<()> {
+        let mut blocks = self.blocks.borrow_mut();
+        let height = blocks.len() + 1;
+        blocks.insert(height, block.clone());
+        Ok(())
+    }
+
+    fn get_by_height(&self, height: BlockHeight) -> StoreResult<Option<Block<T>>> {
+        Ok(self.blocks.borrow().get(&height).cloned())
+    }
+
+    fn latest_hash(&self) -> StoreResult<Option<HashOf<Block<T>>>> {
+        Ok(self.blocks.borrow().values().last().map(Block::hash))
+    }
+
+    fn load_finalized_prefix(&self) -> StoreResult<Vec<Block<T>>> {
+        let ordered: Vec<Block<T>> = self.blocks.borrow().values().cloned().collect();
+        let height = finalized_height(&ordered);
+        Ok(ordered.into_iter().take(height).collect())
+    }
+
+    fn forked(&self, blocks: &[Block<T>]) -> StoreResult<Self> {
+        let mut fresh = InMemoryBlockStore::new();
+        for block in blocks {
+            fresh.append(block)?;
+        }
+        Ok(fresh)
+    }
+}
+
+/// SQLite-backed `BlockStore`, keyed by block hash with columns for height,
+/// epoch and `prev_hash` so a chain can be replayed in order on recovery.
+#[derive(Clone)]
+pub struct SqliteBlockStore<T> {
+    conn: Rc<Connection>,
+    _payload: PhantomData<T>,
+}
+
+impl<T> std::fmt::Debug for SqliteBlockStore<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SqliteBlockStore").finish()
+    }
+}
+
+impl<T> SqliteBlockStore<T>
+where
+    T: Serialize + DeserializeOwned + Clone + PartialEq + Eq + std::fmt::Debug,
+{
+    /// Opens (creating if necessary) a SQLite-backed block store at `path`.
+    pub fn open(path: &str) -> StoreResult<Self> {
+        let conn = Connection::open(path).context(SqliteSnafu)?;
+        Self::create_schema(&conn)?;
+        Ok(SqliteBlockStore {
+            conn: Rc::new(conn),
+            _payload: PhantomData,
+        })
+    }
+
+    fn create_schema(conn: &Connection) -> StoreResult<()> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS blocks (
+                height    INTEGER PRIMARY KEY,
+                hash      BLOB NOT NULL UNIQUE,
+                prev_hash BLOB,
+                epoch     INTEGER NOT NULL,
+                payload   BLOB NOT NULL
+            )",
+        )
+        .context(SqliteSnafu)
+    }
+
+    fn row_to_block(
+        payload: Vec<u8>,
+        prev_hash: Option<Vec<u8>>,
+        epoch: u64,
+    ) -> StoreResult<Block<T>> {
+        let payload: Option<T> = bincode::deserialize(&payload).context(DecodeSnafu)?;
+        Ok(Block {
+            payload,
+            prev_hash: prev_hash.map(|bytes| HashOf::from_bytes(&bytes)),
+            epoch,
+        })
+    }
+}
+
+impl<T> BlockStore<T> for SqliteBlockStore<T>
+where
+    T: Serialize + DeserializeOwned + Clone + PartialEq + Eq + std::fmt::Debug,
+{
+    fn append(&mut self, block: &Block<T>) -> StoreResult<()> {
+        let height: i64 = self
+            .conn
+            .query_row("SELECT COALESCE(MAX(height), 0) FROM blocks", [], |row| {
+                row.get(0)
+            })
+            .context(SqliteSnafu)?;
+        let payload_bytes = bincode::serialize(&block.payload).context(DecodeSnafu)?;
+        let prev_hash_bytes = block.prev_hash.as_ref().map(|h| h.as_bytes().to_vec());
+        self.conn
+            .execute(
+                "INSERT INTO blocks (height, hash, prev_hash, epoch, payload) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    height + 1,
+                    block.hash().as_bytes(),
+                    prev_hash_bytes,
+                    block.epoch as i64,
+                    payload_bytes
+                ],
+            )
+            .context(SqliteSnafu)?;
+        Ok(())
+    }
+
+    fn get_by_height(&self, height: BlockHeight) -> StoreResult<Option<Block<T>>> {
+        self.conn
+            .query_row(
+                "SELECT payload, prev_hash, epoch FROM blocks WHERE height = ?1",
+                params![height as i64],
+                |row| {
+                    Ok((
+                        row.get::<_, Vec<u8>>(0)?,
+                        row.get::<_, Option<Vec<u8>>>(1)?,
+                        row.get::<_, i64>(2)?,
+                    ))
+                },
+            )
+            .optional()
+            .context(SqliteSnafu)?
+            .map(|(payload, prev_hash, epoch)| Self::row_to_block(payload, prev_hash, epoch as u64))
+            .transpose()
+    }
+
+    fn latest_hash(&self) -> StoreResult<Option<HashOf<Block<T>>>> {
+        self.conn
+            .query_row(
+                "SELECT hash FROM blocks ORDER BY height DESC LIMIT 1",
+                [],
+                |row| row.get::<_, Vec<u8>>(0),
+            )
+            .optional()
+            .context(SqliteSnafu)
+            .map(|maybe_bytes| maybe_bytes.map(|bytes| HashOf::from_bytes(&bytes)))
+    }
+
+    fn load_finalized_prefix(&self) -> StoreResult<Vec<Block<T>>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT payload, prev_hash, epoch FROM blocks ORDER BY height ASC")
+            .context(SqliteSnafu)?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, Vec<u8>>(0)?,
+                    row.get::<_, Option<Vec<u8>>>(1)?,
+                    row.get::<_, i64>(2)?,
+                ))
+            })
+            .context(SqliteSnafu)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context(SqliteSnafu)?;
+
+        let blocks = rows
+            .into_iter()
+            .map(|(payload, prev_hash, epoch)| Self::row_to_block(payload, prev_hash, epoch as u64))
+            .collect::<StoreResult<Vec<_>>>()?;
+
+        let height = finalized_height(&blocks);
+        Ok(blocks.into_iter().take(height).collect())
+    }
+
+    fn forked(&self, blocks: &[Block<T>]) -> StoreResult<Self> {
+        let conn = Connection::open_in_memory().context(SqliteSnafu)?;
+        Self::create_schema(&conn)?;
+        let mut fresh = SqliteBlockStore {
+            conn: Rc::new(conn),
+            _payload: PhantomData,
+        };
+        for block in blocks {
+            fresh.append(block)?;
+        }
+        Ok(fresh)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::blockchain::BlockChain;
+
+    #[test]
+    fn in_memory_store_persists_appended_blocks() {
+        let store = InMemoryBlockStore::new();
+        let mut chain: BlockChain<u64, InMemoryBlockStore<u64>> =
+            BlockChain::with_store(store.clone());
+
+        for epoch in 1..=3 {
+            let prev_hash = chain.get_latest_block_hash();
+            let block = Block::new(epoch, prev_hash, epoch);
+            chain.add_block(&block).unwrap();
+        }
+
+        assert_eq!(store.get_by_height(1).unwrap(), Some(Block::genesis_block()));
+        assert_eq!(store.get_by_height(4).unwrap().map(|b| b.epoch), Some(3));
+        assert_eq!(store.get_by_height(5).unwrap(), None);
+
+        // Epochs 0, 1, 2, 3 (genesis plus all three proposed blocks) finalize
+        // the block at epoch 2.
+        let finalized = store.load_finalized_prefix().unwrap();
+        assert_eq!(finalized.len(), 3);
+    }
+
+    #[test]
+    fn recovers_finalized_prefix_and_unfinalized_tail_from_store() {
+        let store = InMemoryBlockStore::new();
+        let mut chain: BlockChain<u64, InMemoryBlockStore<u64>> =
+            BlockChain::with_store(store.clone());
+
+        for epoch in 1..=4 {
+            let prev_hash = chain.get_latest_block_hash();
+            let block = Block::new(epoch, prev_hash, epoch);
+            chain.add_block(&block).unwrap();
+        }
+        drop(chain);
+
+        let recovered: BlockChain<u64, InMemoryBlockStore<u64>> =
+            BlockChain::recover(store).unwrap();
+
+        assert_eq!(recovered.block_height(), 5);
+        assert_eq!(
+            recovered.blocks().iter().map(|b| b.epoch).collect::<Vec<_>>(),
+            vec![0, 1, 2, 3, 4]
+        );
+    }
+}